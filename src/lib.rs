@@ -2,10 +2,19 @@
 
 use std::{
     io::ErrorKind,
-    net::{SocketAddr, TcpListener, UdpSocket},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
 };
 use thiserror::Error;
 
+#[cfg(feature = "shared")]
+mod shared;
+#[cfg(feature = "shared")]
+pub use shared::reserve_port_shared;
+
+/// Interface [`reserve_port`] and friends bind to when the caller doesn't
+/// care which one is used.
+pub(crate) const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
 #[derive(Debug)]
 pub struct ReservedPort<T> {
     number: u16,
@@ -34,14 +43,14 @@ impl<T> AsRef<u16> for ReservedPort<T> {
 
 pub trait Reservable: private::Sealed {
     type Res;
-    fn reserve(port: u16) -> Option<ReservedPort<Self::Res>>;
+    fn reserve(addr: IpAddr, port: u16) -> Option<ReservedPort<Self::Res>>;
 }
 
 impl Reservable for UdpSocket {
     type Res = UdpSocket;
 
-    fn reserve(port: u16) -> Option<ReservedPort<Self::Res>> {
-        let addr: SocketAddr = ([127, 0, 0, 1], port).into();
+    fn reserve(addr: IpAddr, port: u16) -> Option<ReservedPort<Self::Res>> {
+        let addr: SocketAddr = (addr, port).into();
         match UdpSocket::bind(addr) {
             Ok(res) => ReservedPort {
                 number: res.local_addr().unwrap().port(),
@@ -57,8 +66,8 @@ impl Reservable for UdpSocket {
 impl Reservable for TcpListener {
     type Res = TcpListener;
 
-    fn reserve(port: u16) -> Option<ReservedPort<Self::Res>> {
-        let addr: SocketAddr = ([127, 0, 0, 1], port).into();
+    fn reserve(addr: IpAddr, port: u16) -> Option<ReservedPort<Self::Res>> {
+        let addr: SocketAddr = (addr, port).into();
         match TcpListener::bind(addr) {
             Ok(res) => ReservedPort {
                 number: res.local_addr().unwrap().port(),
@@ -71,6 +80,22 @@ impl Reservable for TcpListener {
     }
 }
 
+/// Reports whether `port` is currently free for TCP on `127.0.0.1`, by
+/// binding then immediately releasing it. Unlike [`reserve_tcp_port`], this
+/// does not hold the port open.
+#[inline]
+pub fn is_free_tcp(port: u16) -> bool {
+    TcpListener::reserve(LOCALHOST, port).is_some()
+}
+
+/// Reports whether `port` is currently free for UDP on `127.0.0.1`, by
+/// binding then immediately releasing it. Unlike [`reserve_udp_port`], this
+/// does not hold the port open.
+#[inline]
+pub fn is_free_udp(port: u16) -> bool {
+    UdpSocket::reserve(LOCALHOST, port).is_some()
+}
+
 /// Reserves random UDP port from OS.
 #[inline]
 pub fn reserve_udp_port() -> ReservedPort<UdpSocket> {
@@ -83,7 +108,21 @@ pub fn reserve_tcp_port() -> ReservedPort<TcpListener> {
     reserve_port::<TcpListener, _>(Singleton(0)).unwrap()
 }
 
-pub fn reserve_port<T, P>(mut ports: P) -> Result<ReservedPort<T::Res>, Error>
+/// Reserves a port on `127.0.0.1`. See [`reserve_port_on`] to bind a specific
+/// interface instead.
+#[inline]
+pub fn reserve_port<T, P>(ports: P) -> Result<ReservedPort<T::Res>, Error>
+where
+    T: Reservable,
+    P: ProvidePorts,
+{
+    reserve_port_on::<T, P>(LOCALHOST, ports)
+}
+
+/// Reserves a port that is free on `addr`, the interface to bind to. This is
+/// useful on machines with multiple NICs, or to reserve on `0.0.0.0` or an
+/// IPv6 interface instead of the default loopback address.
+pub fn reserve_port_on<T, P>(addr: IpAddr, mut ports: P) -> Result<ReservedPort<T::Res>, Error>
 where
     T: Reservable,
     P: ProvidePorts,
@@ -95,7 +134,7 @@ where
             return Err(Error::Exhausted(attempts));
         }
         let port = ports.get_port();
-        match T::reserve(port) {
+        match T::reserve(addr, port) {
             Some(x) => break x,
             None => (),
         }
@@ -104,6 +143,277 @@ where
     Ok(port)
 }
 
+/// A single port number reserved simultaneously on both UDP and TCP.
+#[derive(Debug)]
+pub struct ReservedTcpAndUdp {
+    udp: ReservedPort<UdpSocket>,
+    _tcp: ReservedPort<TcpListener>,
+}
+
+impl ReservedTcpAndUdp {
+    /// Takes the port number and releases both reservations.
+    #[inline]
+    pub fn take(self) -> u16 {
+        self.udp.take()
+    }
+
+    /// Returns the port number without releasing either reservation.
+    #[inline]
+    pub fn peek(&self) -> u16 {
+        self.udp.peek()
+    }
+}
+
+/// Reserves a port number that is free on both UDP and TCP at once, on
+/// `127.0.0.1`. See [`reserve_tcp_and_udp_on`] to bind a specific interface
+/// instead.
+#[inline]
+pub fn reserve_tcp_and_udp<P>(ports: P) -> Result<ReservedTcpAndUdp, Error>
+where
+    P: ProvidePorts,
+{
+    reserve_tcp_and_udp_on(LOCALHOST, ports)
+}
+
+/// Reserves a port number that is free on `addr` for both UDP and TCP at
+/// once, retrying the next candidate whenever either transport is already in
+/// use.
+pub fn reserve_tcp_and_udp_on<P>(addr: IpAddr, mut ports: P) -> Result<ReservedTcpAndUdp, Error>
+where
+    P: ProvidePorts,
+{
+    let ports_count = ports.length();
+    let mut attempts = 0;
+    loop {
+        if attempts >= ports_count {
+            return Err(Error::Exhausted(attempts));
+        }
+        let port = ports.get_port();
+        attempts += 1;
+
+        let udp = match UdpSocket::reserve(addr, port) {
+            Some(x) => x,
+            None => continue,
+        };
+        let tcp = match TcpListener::reserve(addr, udp.peek()) {
+            Some(x) => x,
+            None => continue,
+        };
+        return Ok(ReservedTcpAndUdp { udp, _tcp: tcp });
+    }
+}
+
+/// Start of the default range probed by [`reserve_udp_range`] and
+/// [`reserve_tcp_range`], chosen to stay clear of well-known ports.
+pub(crate) const DEFAULT_RANGE_START: u16 = 10_000;
+
+/// End (exclusive) of the default range probed by [`reserve_udp_range`] and
+/// [`reserve_tcp_range`], chosen to stay clear of the ephemeral port zone.
+pub(crate) const DEFAULT_RANGE_END: u16 = 32_000;
+
+/// A contiguous block of reserved ports, numerically adjacent to one another.
+#[derive(Debug)]
+pub struct ReservedRange<T> {
+    ports: Vec<ReservedPort<T>>,
+}
+
+impl<T> ReservedRange<T> {
+    /// Takes the port numbers and release their reservation.
+    #[inline]
+    pub fn take(self) -> Vec<u16> {
+        self.ports.into_iter().map(ReservedPort::take).collect()
+    }
+
+    /// Returns the port numbers without releasing their reservation.
+    #[inline]
+    pub fn peek(&self) -> Vec<u16> {
+        self.ports.iter().map(ReservedPort::peek).collect()
+    }
+}
+
+/// Reserves `count` contiguous UDP ports from the OS, probing candidate start
+/// offsets in the well-known-avoidance range (10000..32000).
+#[inline]
+pub fn reserve_udp_range(count: usize) -> Result<ReservedRange<UdpSocket>, Error> {
+    reserve_range::<UdpSocket, _>(count, DEFAULT_RANGE_START..DEFAULT_RANGE_END)
+}
+
+/// Reserves `count` contiguous TCP ports from the OS, probing candidate start
+/// offsets in the well-known-avoidance range (10000..32000).
+#[inline]
+pub fn reserve_tcp_range(count: usize) -> Result<ReservedRange<TcpListener>, Error> {
+    reserve_range::<TcpListener, _>(count, DEFAULT_RANGE_START..DEFAULT_RANGE_END)
+}
+
+/// Reserves `count` numerically contiguous ports on `127.0.0.1`. See
+/// [`reserve_range_on`] to bind a specific interface instead.
+#[inline]
+pub fn reserve_range<T, P>(count: usize, starts: P) -> Result<ReservedRange<T::Res>, Error>
+where
+    T: Reservable,
+    P: Iterator<Item = u16>,
+{
+    reserve_range_on::<T, P>(LOCALHOST, count, starts)
+}
+
+/// Reserves `count` numerically contiguous ports that are free on `addr`,
+/// probing candidate start offsets drawn from `starts`. Sockets acquired for
+/// a candidate that turns out to be only partially free are dropped (and so
+/// released) before the next candidate is tried.
+pub fn reserve_range_on<T, P>(
+    addr: IpAddr,
+    count: usize,
+    starts: P,
+) -> Result<ReservedRange<T::Res>, Error>
+where
+    T: Reservable,
+    P: Iterator<Item = u16>,
+{
+    if count == 0 {
+        return Ok(ReservedRange { ports: Vec::new() });
+    }
+
+    let mut attempts = 0;
+    'candidates: for start in starts {
+        let end = match start.checked_add(count as u16 - 1) {
+            Some(end) => end,
+            None => continue,
+        };
+        attempts += 1;
+
+        let mut reserved = Vec::with_capacity(count);
+        for port in start..=end {
+            match T::reserve(addr, port) {
+                Some(x) => reserved.push(x),
+                None => continue 'candidates,
+            }
+        }
+        return Ok(ReservedRange { ports: reserved });
+    }
+
+    Err(Error::Exhausted(attempts))
+}
+
+/// A port number held open by `count` UDP sockets bound together via
+/// `SO_REUSEPORT`, letting the kernel load-balance datagrams across them.
+#[derive(Debug)]
+pub struct ReservedReusePort {
+    number: u16,
+    sockets: Vec<UdpSocket>,
+}
+
+impl ReservedReusePort {
+    /// Takes the underlying sockets. The port stays bound for as long as the
+    /// caller keeps them open.
+    #[inline]
+    pub fn take(self) -> Vec<UdpSocket> {
+        self.sockets
+    }
+
+    /// Returns the port number without releasing the reservation.
+    #[inline]
+    pub fn peek(&self) -> u16 {
+        self.number
+    }
+}
+
+/// Reserves `count` UDP sockets bound to the same port via `SO_REUSEPORT`, on
+/// `127.0.0.1`. See [`reserve_reuseport_udp_on`] to bind a specific interface
+/// instead.
+#[inline]
+pub fn reserve_reuseport_udp<P>(count: usize, ports: P) -> Result<ReservedReusePort, Error>
+where
+    P: ProvidePorts,
+{
+    reserve_reuseport_udp_on(LOCALHOST, count, ports)
+}
+
+/// Reserves `count` UDP sockets bound to the same port on `addr` via
+/// `SO_REUSEPORT`, so the kernel load-balances datagrams across them. All
+/// `count` sockets are kept alive in the returned [`ReservedReusePort`] until
+/// [`ReservedReusePort::take`] hands them to the caller.
+///
+/// Returns [`Error::ReusePortUnsupported`] on platforms without
+/// `SO_REUSEPORT` (anything other than Unix).
+#[cfg(unix)]
+pub fn reserve_reuseport_udp_on<P>(
+    addr: IpAddr,
+    count: usize,
+    mut ports: P,
+) -> Result<ReservedReusePort, Error>
+where
+    P: ProvidePorts,
+{
+    let ports_count = ports.length();
+    let mut attempts = 0;
+
+    'candidates: loop {
+        if attempts >= ports_count {
+            return Err(Error::Exhausted(attempts));
+        }
+        let mut port = ports.get_port();
+        attempts += 1;
+
+        let mut sockets = Vec::with_capacity(count);
+        for i in 0..count {
+            match bind_reuseport(addr, port) {
+                Some(socket) => {
+                    if i == 0 {
+                        port = socket.local_addr().unwrap().port();
+                    }
+                    sockets.push(socket);
+                }
+                None => continue 'candidates,
+            }
+        }
+
+        return Ok(ReservedReusePort {
+            number: port,
+            sockets,
+        });
+    }
+}
+
+/// See the Unix implementation above; `SO_REUSEPORT` has no equivalent on
+/// other platforms.
+#[cfg(not(unix))]
+pub fn reserve_reuseport_udp_on<P>(
+    _addr: IpAddr,
+    _count: usize,
+    _ports: P,
+) -> Result<ReservedReusePort, Error>
+where
+    P: ProvidePorts,
+{
+    Err(Error::ReusePortUnsupported)
+}
+
+#[cfg(unix)]
+fn bind_reuseport(addr: IpAddr, port: u16) -> Option<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let domain = match addr {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    };
+
+    let socket =
+        Socket::new(domain, Type::DGRAM, Some(Protocol::UDP)).expect("failed to create socket");
+    socket
+        .set_reuse_address(true)
+        .expect("failed to set SO_REUSEADDR");
+    socket
+        .set_reuse_port(true)
+        .expect("failed to set SO_REUSEPORT");
+
+    let addr: SocketAddr = (addr, port).into();
+    match socket.bind(&addr.into()) {
+        Ok(()) => Some(socket.into()),
+        Err(x) if x.kind() == ErrorKind::AddrInUse => None,
+        Err(x) => panic!("{}", x),
+    }
+}
+
 pub trait ProvidePorts {
     fn get_port(&mut self) -> u16;
     fn length(&self) -> usize;
@@ -134,10 +444,83 @@ where
     }
 }
 
+/// A [`ProvidePorts`] source that skips any port failing `predicate`, e.g. to
+/// exclude privileged ports, a blacklist, or only-even ports before probing.
+///
+/// [`ProvidePorts::length`] must be known up front so [`Error::Exhausted`]
+/// still terminates correctly, so [`Filtered::new`] eagerly drains the
+/// wrapped source and keeps only the ports that pass `predicate`.
+pub struct Filtered<P, F> {
+    ports: std::vec::IntoIter<u16>,
+    length: usize,
+    _marker: std::marker::PhantomData<(P, F)>,
+}
+
+impl<P, F> std::fmt::Debug for Filtered<P, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filtered").field("length", &self.length).finish()
+    }
+}
+
+impl<P, F> Filtered<P, F>
+where
+    P: ProvidePorts,
+    F: FnMut(u16) -> bool,
+{
+    /// Wraps `ports`, keeping only the ports for which `predicate` returns
+    /// `true`.
+    pub fn new(mut ports: P, mut predicate: F) -> Self {
+        let total = ports.length();
+        let survivors: Vec<u16> = (0..total)
+            .map(|_| ports.get_port())
+            .filter(|&port| predicate(port))
+            .collect();
+
+        Filtered {
+            length: survivors.len(),
+            ports: survivors.into_iter(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P, F> ProvidePorts for Filtered<P, F> {
+    fn get_port(&mut self) -> u16 {
+        self.ports.next().unwrap()
+    }
+
+    fn length(&self) -> usize {
+        self.length
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("failed to find usable port after {0} attempts")]
     Exhausted(usize),
+
+    /// Returned by [`reserve_reuseport_udp`] on platforms without
+    /// `SO_REUSEPORT`.
+    #[error("SO_REUSEPORT is not supported on this platform")]
+    ReusePortUnsupported,
+
+    /// Failed to read, lock, or write the shared allocator's state file.
+    #[cfg(feature = "shared")]
+    #[error("failed to access shared allocator state at {path:?}: {source}")]
+    SharedStateIo {
+        path: std::path::PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The shared allocator's state file contained invalid JSON.
+    #[cfg(feature = "shared")]
+    #[error("failed to parse shared allocator state at {path:?}: {source}")]
+    SharedStateFormat {
+        path: std::path::PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
 }
 
 mod private {
@@ -195,4 +578,116 @@ mod tests {
             "failed to find usable port after 1 attempts"
         );
     }
+
+    #[test]
+    fn test_range_is_contiguous_for_udp() {
+        let range = reserve_udp_range(3).unwrap();
+        let ports = range.take();
+
+        assert_eq!(ports, [ports[0], ports[0] + 1, ports[0] + 2]);
+    }
+
+    #[test]
+    fn test_range_is_contiguous_for_tcp() {
+        let range = reserve_tcp_range(3).unwrap();
+        let ports = range.take();
+
+        assert_eq!(ports, [ports[0], ports[0] + 1, ports[0] + 2]);
+    }
+
+    #[test]
+    fn test_range_exhausted_when_no_candidate_fits() {
+        let port = reserve_udp_port();
+
+        let error = reserve_range::<UdpSocket, _>(2, [port.peek()].into_iter()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "failed to find usable port after 1 attempts"
+        );
+    }
+
+    #[test]
+    fn test_reserve_port_on_specific_interface() {
+        let port = reserve_port_on::<UdpSocket, _>(LOCALHOST, Singleton(0)).unwrap();
+
+        assert!(port.peek() > 0);
+    }
+
+    #[test]
+    fn test_reserve_tcp_and_udp_picks_same_port() {
+        let busy = reserve_udp_port();
+        let free = reserve_tcp_port().take();
+
+        let port = reserve_tcp_and_udp([busy.peek(), free].into_iter()).unwrap();
+
+        assert_eq!(port.peek(), free);
+    }
+
+    #[test]
+    fn test_reserve_tcp_and_udp_exhausted_when_port_taken() {
+        let port = reserve_tcp_port();
+
+        let error = reserve_tcp_and_udp(Singleton(port.peek())).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "failed to find usable port after 1 attempts"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_reserve_reuseport_udp_shares_one_port() {
+        let reserved = reserve_reuseport_udp(4, Singleton(0)).unwrap();
+        let number = reserved.peek();
+
+        let sockets = reserved.take();
+
+        assert_eq!(sockets.len(), 4);
+        assert!(sockets
+            .iter()
+            .all(|s| s.local_addr().unwrap().port() == number));
+    }
+
+    #[test]
+    #[cfg(not(unix))]
+    fn test_reserve_reuseport_udp_unsupported_off_unix() {
+        let error = reserve_reuseport_udp(4, Singleton(0)).unwrap_err();
+
+        assert_eq!(error.to_string(), "SO_REUSEPORT is not supported on this platform");
+    }
+
+    #[test]
+    fn test_is_free_reports_held_port_as_taken() {
+        let port = reserve_udp_port();
+
+        assert!(!is_free_udp(port.peek()));
+    }
+
+    #[test]
+    fn test_is_free_does_not_hold_the_port() {
+        let port = reserve_tcp_port().take();
+
+        assert!(is_free_tcp(port));
+        TcpListener::reserve(LOCALHOST, port).unwrap();
+    }
+
+    #[test]
+    fn test_filtered_skips_ports_failing_predicate() {
+        let candidates = [8000, 8001, 8002, 8003].into_iter();
+        let ports = Filtered::new(candidates, |port| port % 2 == 0);
+
+        let port = reserve_port::<TcpListener, _>(ports).unwrap();
+
+        assert!(port.peek() % 2 == 0);
+    }
+
+    #[test]
+    fn test_filtered_adjusts_length_for_exhausted() {
+        let candidates = [8000, 8001].into_iter();
+        let ports = Filtered::new(candidates, |port| port % 2 == 0);
+
+        assert_eq!(ports.length(), 1);
+    }
 }
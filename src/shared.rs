@@ -0,0 +1,199 @@
+//! Cross-process persistent port allocator.
+//!
+//! The in-memory API only prevents collisions within a single process: two
+//! independent processes racing to reserve "port 0" can still end up with
+//! overlapping choices once they rebind. [`reserve_port_shared`] instead
+//! hands out ports from a shared, file-locked state file so independent
+//! processes (e.g. parallel test runners) never hand out the same port
+//! within a TTL window.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    net::{TcpListener, UdpSocket},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{Reservable, DEFAULT_RANGE_END, DEFAULT_RANGE_START, LOCALHOST};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    next: u16,
+    allocated: HashMap<u16, Allocation>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            next: DEFAULT_RANGE_START,
+            allocated: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Allocation {
+    size: u16,
+    expires: u64,
+}
+
+/// Reserves `count` contiguous ports, recorded in the state file at `path` so
+/// that other processes sharing the same file won't be handed the same
+/// ports again until `ttl` elapses.
+///
+/// Unlike [`crate::reserve_port`], the returned ports are not held open:
+/// each candidate is confirmed free with a test bind (reusing
+/// [`Reservable::reserve`]) and immediately released, with the state file
+/// itself acting as the reservation for the duration of `ttl`.
+pub fn reserve_port_shared(
+    path: impl AsRef<Path>,
+    ttl: Duration,
+    count: usize,
+) -> Result<Vec<u16>, crate::Error> {
+    let path = path.as_ref();
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(path)
+        .map_err(|source| io_error(path, source))?;
+
+    file.lock_exclusive().map_err(|source| io_error(path, source))?;
+
+    let result = (|| {
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)
+            .map_err(|source| io_error(path, source))?;
+
+        let mut state: State = if buf.trim().is_empty() {
+            State::default()
+        } else {
+            serde_json::from_str(&buf).map_err(|source| format_error(path, source))?
+        };
+
+        let now = now();
+        state.allocated.retain(|_, a| a.expires > now);
+
+        let start = allocate(&mut state, count as u16, now, ttl)?;
+        let ports = (start..start + count as u16).collect();
+
+        let json = serde_json::to_string(&state).map_err(|source| format_error(path, source))?;
+        file.set_len(0).map_err(|source| io_error(path, source))?;
+        file.seek(SeekFrom::Start(0))
+            .map_err(|source| io_error(path, source))?;
+        file.write_all(json.as_bytes())
+            .map_err(|source| io_error(path, source))?;
+
+        Ok(ports)
+    })();
+
+    FileExt::unlock(&file).map_err(|source| io_error(path, source))?;
+
+    result
+}
+
+/// Scans forward from `state.next`, wrapping back to [`DEFAULT_RANGE_START`]
+/// at the high bound, for the first `count`-port block that doesn't overlap
+/// a live allocation and is genuinely free on the wire.
+fn allocate(state: &mut State, count: u16, now: u64, ttl: Duration) -> Result<u16, crate::Error> {
+    let span = DEFAULT_RANGE_END - DEFAULT_RANGE_START;
+    let mut start = state.next;
+    let mut attempts = 0usize;
+
+    loop {
+        if attempts >= span as usize {
+            return Err(crate::Error::Exhausted(attempts));
+        }
+
+        let fits = match start.checked_add(count) {
+            Some(end) => end <= DEFAULT_RANGE_END,
+            None => false,
+        };
+
+        if fits && !overlaps_live_allocation(state, start, count, now) && is_free(start, count) {
+            let end = start + count;
+            state.allocated.insert(
+                start,
+                Allocation {
+                    size: count,
+                    expires: now + ttl.as_secs(),
+                },
+            );
+            state.next = if end >= DEFAULT_RANGE_END {
+                DEFAULT_RANGE_START
+            } else {
+                end
+            };
+            return Ok(start);
+        }
+
+        attempts += 1;
+        start = if fits {
+            start + 1
+        } else {
+            DEFAULT_RANGE_START
+        };
+    }
+}
+
+fn overlaps_live_allocation(state: &State, start: u16, count: u16, now: u64) -> bool {
+    let end = start + count;
+    state.allocated.iter().any(|(&other_start, a)| {
+        a.expires > now && start < other_start + a.size && other_start < end
+    })
+}
+
+fn is_free(start: u16, count: u16) -> bool {
+    (start..start + count).all(|port| {
+        UdpSocket::reserve(LOCALHOST, port).is_some() && TcpListener::reserve(LOCALHOST, port).is_some()
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> crate::Error {
+    crate::Error::SharedStateIo {
+        path: path.to_owned(),
+        source,
+    }
+}
+
+fn format_error(path: &Path, source: serde_json::Error) -> crate::Error {
+    crate::Error::SharedStateFormat {
+        path: path.to_owned(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocations_do_not_overlap() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("getport-shared-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let first = reserve_port_shared(&path, Duration::from_secs(60), 2).unwrap();
+        let second = reserve_port_shared(&path, Duration::from_secs(60), 2).unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        assert!(first.iter().all(|p| !second.contains(p)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -37,3 +37,12 @@ fn test_port_asref_u16() {
     let _: &dyn AsRef<u16> = &port;
     assert_eq!(*port.as_ref(), port.peek())
 }
+
+#[test]
+fn test_basic_usage_scenario_for_tcp_and_udp() {
+    let port = getport::reserve_tcp_and_udp([8000, 8080].into_iter()).unwrap();
+    let number = port.take();
+
+    UdpSocket::bind(format!("127.0.0.1:{number}")).unwrap();
+    TcpListener::bind(format!("127.0.0.1:{number}")).unwrap();
+}